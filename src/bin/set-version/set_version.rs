@@ -68,6 +68,10 @@ pub struct VersionArgs {
     #[arg(long)]
     locked: bool,
 
+    /// Coloring: auto, always, never
+    #[arg(long, value_name = "WHEN", value_enum, hide_possible_values = true)]
+    color: Option<clap::ColorChoice>,
+
     /// Unstable (nightly-only) flags
     #[arg(short = 'Z', value_name = "FLAG", global = true, value_enum)]
     unstable_features: Vec<UnstableOptions>,
@@ -97,9 +101,18 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
         exclude,
         locked,
         offline,
+        color,
         unstable_features: _,
     } = args;
 
+    if let Some(color) = color {
+        concolor_control::set(match color {
+            clap::ColorChoice::Auto => concolor_control::ColorChoice::Auto,
+            clap::ColorChoice::Always => concolor_control::ColorChoice::Always,
+            clap::ColorChoice::Never => concolor_control::ColorChoice::Never,
+        });
+    }
+
     let target = match (target, bump) {
         (None, None) => TargetVersion::Relative(BumpLevel::Release),
         (None, Some(level)) => TargetVersion::Relative(level),
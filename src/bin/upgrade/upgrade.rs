@@ -5,9 +5,9 @@ use std::path::PathBuf;
 
 use anyhow::Context as _;
 use cargo_edit::{
-    get_compatible_dependency, get_latest_dependency, registry_url, set_dep_version, shell_note,
-    shell_status, shell_warn, shell_write_stdout, CargoResult, CertsSource, CrateSpec, Dependency,
-    IndexCache, LocalManifest, RustVersion, Source,
+    get_compatible_dependency, get_latest_dependency, http_config, registry_index, registry_token,
+    set_dep_version, shell_note, shell_status, shell_warn, shell_write_stdout, CargoResult,
+    CertsSource, CrateSpec, Dependency, IndexCache, LocalManifest, RustVersion, Source,
 };
 use clap::Args;
 use indexmap::IndexMap;
@@ -42,6 +42,10 @@ pub struct UpgradeArgs {
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
 
+    /// Coloring: auto, always, never
+    #[arg(long, value_name = "WHEN", value_enum, hide_possible_values = true)]
+    color: Option<clap::ColorChoice>,
+
     /// Unstable (nightly-only) flags
     #[arg(short = 'Z', value_name = "FLAG", global = true, value_enum)]
     unstable_features: Vec<UnstableOptions>,
@@ -156,11 +160,19 @@ enum UnstableOptions {}
 /// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
 /// messages.
 fn exec(args: UpgradeArgs) -> CargoResult<()> {
+    if let Some(color) = args.color {
+        concolor_control::set(match color {
+            clap::ColorChoice::Auto => concolor_control::ColorChoice::Auto,
+            clap::ColorChoice::Always => concolor_control::ColorChoice::Always,
+            clap::ColorChoice::Never => concolor_control::ColorChoice::Never,
+        });
+    }
     let offline = false;
     let mut index = IndexCache::new(CertsSource::Native);
 
     let metadata = resolve_ws(args.manifest_path.as_deref(), args.locked, offline)?;
     let root_manifest_path = metadata.workspace_root.as_std_path().join("Cargo.toml");
+    index.set_http_config(http_config(&root_manifest_path)?);
     let manifests = find_ws_members(&metadata);
     let mut manifests = manifests
         .into_iter()
@@ -288,8 +300,25 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
                 {
                     // Update indices for any alternative registries, unless
                     // we're offline.
-                    let registry_url = registry_url(&manifest_path, dependency.registry())?;
-                    let index = index.index(&registry_url)?;
+                    let registry = registry_index(&manifest_path, dependency.registry())?;
+                    if !index.contains(&registry) {
+                        if registry.url.scheme() != "file" {
+                            // Remote crates are fetched on demand, so this is the closest we get
+                            // to cargo's index update
+                            // Like cargo, name the source actually read, which may be a mirror
+                            let name = match registry.name.as_str() {
+                                "crates-io" => "crates.io".to_owned(),
+                                name => format!("`{name}`"),
+                            };
+                            shell_status("Updating", &format!("{name} index"))?;
+                        }
+                        let token = registry_token(dependency.registry())?;
+                        let index = index.index(&registry)?;
+                        if let Some(token) = token {
+                            index.set_token(token);
+                        }
+                    }
+                    let index = index.index(&registry)?;
                     let latest_compatible = VersionReq::parse(&old_version_req)
                         .ok()
                         .and_then(|old_version_req| {
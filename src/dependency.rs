@@ -391,8 +391,41 @@ impl Dependency {
     /// Modify existing entry to match this dependency
     pub fn update_toml(&self, crate_root: &Path, key: &mut KeyMut, item: &mut toml_edit::Item) {
         if str_or_1_len_table(item) {
-            // Nothing to preserve
+            // Nothing to preserve but comments and the table style
+            let existing_decor = item.as_value().map(|v| v.decor().clone());
+            let existing_table = item.as_table().cloned();
             *item = self.to_toml(crate_root);
+            if let (Some(decor), Some(value)) = (existing_decor, item.as_value_mut()) {
+                *value.decor_mut() = decor;
+            }
+            if let Some(existing_table) = existing_table {
+                let mut table = match std::mem::take(item) {
+                    toml_edit::Item::Value(toml_edit::Value::InlineTable(table)) => {
+                        table.into_table()
+                    }
+                    toml_edit::Item::Value(version) => {
+                        let mut table = toml_edit::Table::new();
+                        table.insert("version", toml_edit::Item::Value(version));
+                        table
+                    }
+                    other => unreachable!("Invalid dependency type: {}", other.type_name()),
+                };
+                for (key, value) in table.iter_mut() {
+                    let existing_decor = existing_table
+                        .get(key.get())
+                        .and_then(|i| i.as_value())
+                        .map(|v| v.decor().clone());
+                    if let (Some(decor), Some(value)) = (existing_decor, value.as_value_mut()) {
+                        *value.decor_mut() = decor;
+                    }
+                }
+                *table.decor_mut() = existing_table.decor().clone();
+                if let Some(position) = existing_table.position() {
+                    table.set_position(position);
+                }
+                table.set_dotted(existing_table.is_dotted());
+                *item = toml_edit::Item::Table(table);
+            }
             key.fmt();
         } else if let Some(table) = item.as_table_like_mut() {
             match &self.source {
@@ -457,7 +490,6 @@ impl Dependency {
                         "tag",
                         "rev",
                         "package",
-                        "default-features",
                     ] {
                         table.remove(key);
                     }
@@ -481,6 +513,12 @@ impl Dependency {
                 Some(v) => {
                     overwrite_value(table, "default-features", v);
                 }
+                // Inherited entries only layer on top of the workspace, keep what is there
+                None if matches!(self.source, Some(Source::Workspace(_))) => {
+                    if table.contains_key("default-features") {
+                        table.set_dotted(false);
+                    }
+                }
                 None => {
                     table.remove("default-features");
                 }
@@ -980,6 +1018,116 @@ mod tests {
         verify_roundtrip(&crate_root, key, &item);
     }
 
+    #[test]
+    fn update_toml_preserves_comments() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let mut manifest: toml_edit::Document = r#"dep = "1.0"  # keep in sync with foo
+"#
+        .parse()
+        .unwrap();
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("2.0"))
+            .set_optional(true);
+        let (mut key, item) = manifest.get_key_value_mut("dep").unwrap();
+        dep.update_toml(&crate_root, &mut key, item);
+
+        assert_eq!(
+            manifest.to_string(),
+            r#"dep = { version = "2.0", optional = true }  # keep in sync with foo
+"#
+        );
+    }
+
+    #[test]
+    fn update_toml_preserves_std_table() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let mut manifest: toml_edit::Document = r#"[dependencies.dep]
+version = "1.0"  # keep in sync with foo
+
+[dev-dependencies]
+"#
+        .parse()
+        .unwrap();
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("2.0"))
+            .set_optional(true);
+        let table = manifest["dependencies"].as_table_mut().unwrap();
+        let (mut key, item) = table.get_key_value_mut("dep").unwrap();
+        dep.update_toml(&crate_root, &mut key, item);
+
+        assert_eq!(
+            manifest.to_string(),
+            r#"[dependencies.dep]
+version = "2.0"  # keep in sync with foo
+optional = true
+
+[dev-dependencies]
+"#
+        );
+    }
+
+    #[test]
+    fn update_toml_preserves_artifact_keys() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let mut manifest: toml_edit::Document =
+            r#"dep = { version = "1.0", artifact = "bin", lib = false, target = "x86_64-unknown-linux-gnu" }"#
+                .parse()
+                .unwrap();
+        let dep = Dependency::new("dep").set_source(RegistrySource::new("2.0"));
+        let (mut key, item) = manifest.get_key_value_mut("dep").unwrap();
+        dep.update_toml(&crate_root, &mut key, item);
+
+        assert_eq!(
+            manifest.to_string(),
+            r#"dep = { version = "2.0", artifact = "bin", lib = false, target = "x86_64-unknown-linux-gnu" }
+"#
+        );
+    }
+
+    #[test]
+    fn update_toml_merges_inherited_features() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let mut manifest: toml_edit::Document =
+            r#"dep = { workspace = true, default-features = false, features = ["a"] }"#
+                .parse()
+                .unwrap();
+        let dep = Dependency::new("dep")
+            .set_source(WorkspaceSource::new())
+            .set_default_features(false)
+            .set_features(vec!["b".to_owned()]);
+        let (mut key, item) = manifest.get_key_value_mut("dep").unwrap();
+        dep.update_toml(&crate_root, &mut key, item);
+
+        assert_eq!(
+            manifest.to_string(),
+            r#"dep = { workspace = true, default-features = false, features = ["a", "b"] }
+"#
+        );
+    }
+
+    #[test]
+    fn update_toml_keeps_inherited_default_features() {
+        let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
+            .expect("root exists");
+        let mut manifest: toml_edit::Document =
+            r#"dep = { workspace = true, default-features = false }"#
+                .parse()
+                .unwrap();
+        let dep = Dependency::new("dep").set_source(WorkspaceSource::new());
+        let (mut key, item) = manifest.get_key_value_mut("dep").unwrap();
+        dep.update_toml(&crate_root, &mut key, item);
+
+        assert_eq!(
+            manifest.to_string(),
+            r#"dep = { workspace = true, default-features = false }
+"#
+        );
+    }
+
     #[test]
     fn paths_with_forward_slashes_are_left_as_is() {
         let crate_root = dunce::canonicalize(std::env::current_dir().unwrap().join(Path::new("/")))
@@ -4,6 +4,7 @@ use tame_index::utils::flock::FileLock;
 use url::Url;
 
 use super::errors::*;
+use super::{HttpConfig, IndexKind, RegistryIndex};
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CertsSource {
@@ -16,6 +17,7 @@ pub enum CertsSource {
 
 pub struct IndexCache {
     certs_source: CertsSource,
+    http: HttpConfig,
     index: std::collections::HashMap<Url, AnyIndexCache>,
 }
 
@@ -24,13 +26,20 @@ impl IndexCache {
     pub fn new(certs_source: CertsSource) -> Self {
         Self {
             certs_source,
+            http: HttpConfig::default(),
             index: Default::default(),
         }
     }
 
+    /// Apply cargo's `[http]` settings to indexes opened after this call
+    #[inline]
+    pub fn set_http_config(&mut self, http: HttpConfig) {
+        self.http = http;
+    }
+
     /// Determines if the specified crate exists in the crates.io index
     #[inline]
-    pub fn has_krate(&mut self, registry: &Url, name: &str) -> CargoResult<bool> {
+    pub fn has_krate(&mut self, registry: &RegistryIndex, name: &str) -> CargoResult<bool> {
         self.index(registry)?.has_krate(name)
     }
 
@@ -38,7 +47,7 @@ impl IndexCache {
     #[inline]
     pub fn has_krate_version(
         &mut self,
-        registry: &Url,
+        registry: &RegistryIndex,
         name: &str,
         version: &str,
     ) -> CargoResult<Option<bool>> {
@@ -46,22 +55,32 @@ impl IndexCache {
     }
 
     #[inline]
-    pub fn update_krate(&mut self, registry: &Url, name: &str) -> CargoResult<()> {
+    pub fn update_krate(&mut self, registry: &RegistryIndex, name: &str) -> CargoResult<()> {
         self.index(registry)?.update_krate(name);
         Ok(())
     }
 
-    pub fn krate(&mut self, registry: &Url, name: &str) -> CargoResult<Option<IndexKrate>> {
+    pub fn krate(
+        &mut self,
+        registry: &RegistryIndex,
+        name: &str,
+    ) -> CargoResult<Option<IndexKrate>> {
         self.index(registry)?.krate(name)
     }
 
-    pub fn index<'s>(&'s mut self, registry: &Url) -> CargoResult<&'s mut AnyIndexCache> {
-        if !self.index.contains_key(registry) {
-            let index = AnyIndex::open(registry, self.certs_source)?;
+    /// Whether the index for `registry` has already been opened
+    #[inline]
+    pub fn contains(&self, registry: &RegistryIndex) -> bool {
+        self.index.contains_key(&registry.url)
+    }
+
+    pub fn index<'s>(&'s mut self, registry: &RegistryIndex) -> CargoResult<&'s mut AnyIndexCache> {
+        if !self.index.contains_key(&registry.url) {
+            let index = AnyIndex::open(registry, self.certs_source, &self.http)?;
             let index = AnyIndexCache::new(index);
-            self.index.insert(registry.clone(), index);
+            self.index.insert(registry.url.clone(), index);
         }
-        Ok(self.index.get_mut(registry).unwrap())
+        Ok(self.index.get_mut(&registry.url).unwrap())
     }
 }
 
@@ -97,6 +116,12 @@ impl AnyIndexCache {
         self.cache.remove(name);
     }
 
+    /// Token to authenticate with, should the registry require it
+    #[inline]
+    pub fn set_token(&mut self, token: String) {
+        self.index.set_token(token);
+    }
+
     pub fn krate(&mut self, name: &str) -> CargoResult<Option<IndexKrate>> {
         if let Some(entry) = self.cache.get(name) {
             return Ok(entry.clone());
@@ -110,24 +135,40 @@ impl AnyIndexCache {
 
 pub enum AnyIndex {
     Local(LocalIndex),
+    Directory(DirectoryIndex),
     Remote(RemoteIndex),
 }
 
 impl AnyIndex {
-    pub fn open(url: &Url, certs_source: CertsSource) -> CargoResult<Self> {
-        if url.scheme() == "file" {
-            LocalIndex::open(url).map(Self::Local)
-        } else {
-            RemoteIndex::open(url, certs_source).map(Self::Remote)
+    pub fn open(
+        registry: &RegistryIndex,
+        certs_source: CertsSource,
+        http: &HttpConfig,
+    ) -> CargoResult<Self> {
+        let url = &registry.url;
+        match registry.kind {
+            IndexKind::Directory => DirectoryIndex::open(url).map(Self::Directory),
+            IndexKind::LocalRegistry => LocalIndex::open(url).map(Self::Local),
+            IndexKind::Registry if url.scheme() == "file" => LocalIndex::open(url).map(Self::Local),
+            IndexKind::Registry => RemoteIndex::open(url, certs_source, http).map(Self::Remote),
         }
     }
 
     pub(crate) fn krate(&mut self, name: &str) -> CargoResult<Option<IndexKrate>> {
         match self {
             Self::Local(index) => index.krate(name),
+            Self::Directory(index) => index.krate(name),
             Self::Remote(index) => index.krate(name),
         }
     }
+
+    pub(crate) fn set_token(&mut self, token: String) {
+        match self {
+            // Local sources are read straight from disk
+            Self::Local(_) | Self::Directory(_) => {}
+            Self::Remote(index) => index.token = Some(token),
+        }
+    }
 }
 
 pub struct LocalIndex {
@@ -163,15 +204,98 @@ impl LocalIndex {
     }
 }
 
+/// Crates unpacked into a directory, like those written by `cargo vendor`
+pub struct DirectoryIndex {
+    root: std::path::PathBuf,
+}
+
+impl DirectoryIndex {
+    pub fn open(url: &Url) -> CargoResult<Self> {
+        let root = url
+            .to_file_path()
+            .map_err(|()| anyhow::format_err!("invalid directory source {url}"))?;
+        anyhow::ensure!(
+            root.is_dir(),
+            "directory source {} does not exist",
+            root.display()
+        );
+        Ok(Self { root })
+    }
+
+    pub(crate) fn krate(&mut self, name: &str) -> CargoResult<Option<IndexKrate>> {
+        let mut versions = Vec::new();
+        for entry in std::fs::read_dir(&self.root)? {
+            let entry = entry?;
+            // Crates are unpacked as `<name>` or `<name>-<version>`
+            let dir_name = entry.file_name();
+            let dir_name = dir_name.to_string_lossy();
+            if dir_name != name && !dir_name.starts_with(&format!("{name}-")) {
+                continue;
+            }
+
+            let manifest_path = entry.path().join("Cargo.toml");
+            if !manifest_path.is_file() {
+                continue;
+            }
+            let manifest = std::fs::read_to_string(&manifest_path)?;
+            let manifest = manifest
+                .parse::<toml_edit::Document>()
+                .with_context(|| format!("Unable to parse {}", manifest_path.display()))?;
+            let Some(package) = manifest.get("package") else {
+                continue;
+            };
+            if package.get("name").and_then(|n| n.as_str()) != Some(name) {
+                continue;
+            }
+            let Some(version) = package.get("version").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let rust_version = package.get("rust-version").and_then(|v| v.as_str());
+            let checksum = std::fs::read_to_string(entry.path().join(".cargo-checksum.json"))
+                .ok()
+                .and_then(|c| serde_json::from_str::<serde_json::Value>(&c).ok())
+                .and_then(|c| c["package"].as_str().map(ToOwned::to_owned))
+                .unwrap_or_else(|| "0".repeat(64));
+
+            let version = serde_json::json!({
+                "name": name,
+                "vers": version,
+                "deps": [],
+                "cksum": checksum,
+                "features": {},
+                "rust_version": rust_version,
+            });
+            versions.push(serde_json::from_value(version)?);
+        }
+
+        if versions.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(IndexKrate { versions }))
+        }
+    }
+}
+
 pub struct RemoteIndex {
     index: tame_index::SparseIndex,
     client: tame_index::external::reqwest::blocking::Client,
     lock: FileLock,
     etags: Vec<(String, String)>,
+    token: Option<String>,
+    auth_required: bool,
+    retry: u32,
+}
+
+/// Matches cargo's default for `net.retry`
+const DEFAULT_RETRY: u32 = 3;
+
+fn is_spurious_status(status: tame_index::external::reqwest::StatusCode) -> bool {
+    status.is_server_error()
+        || status == tame_index::external::reqwest::StatusCode::TOO_MANY_REQUESTS
 }
 
 impl RemoteIndex {
-    pub fn open(url: &Url, certs_source: CertsSource) -> CargoResult<Self> {
+    pub fn open(url: &Url, certs_source: CertsSource, http: &HttpConfig) -> CargoResult<Self> {
         let url = url.to_string();
         let url = tame_index::IndexUrl::NonCratesIo(std::borrow::Cow::Owned(url));
         let index = tame_index::SparseIndex::new(tame_index::IndexLocation::new(url))?;
@@ -179,11 +303,33 @@ impl RemoteIndex {
         let client = {
             let builder = tame_index::external::reqwest::blocking::ClientBuilder::new();
 
-            let builder = match certs_source {
+            let mut builder = match certs_source {
                 CertsSource::Webpki => builder.tls_built_in_webpki_certs(true),
                 CertsSource::Native => builder.tls_built_in_native_certs(true),
             };
 
+            // Without an explicit proxy, reqwest falls back to `http_proxy`, `HTTPS_PROXY`, etc
+            if let Some(proxy) = &http.proxy {
+                let proxy = tame_index::external::reqwest::Proxy::all(proxy)
+                    .with_context(|| format!("Invalid `http.proxy` {proxy}"))?;
+                builder = builder.proxy(proxy);
+            }
+
+            if let Some(timeout) = http.timeout {
+                builder = builder.timeout(timeout);
+            }
+
+            if let Some(cainfo) = &http.cainfo {
+                let pem = std::fs::read(cainfo).with_context(|| {
+                    format!("Unable to read `http.cainfo` {}", cainfo.display())
+                })?;
+                let certs = tame_index::external::reqwest::Certificate::from_pem_bundle(&pem)
+                    .with_context(|| format!("Invalid `http.cainfo` {}", cainfo.display()))?;
+                for cert in certs {
+                    builder = builder.add_root_certificate(cert);
+                }
+            }
+
             builder.build()?
         };
 
@@ -194,9 +340,49 @@ impl RemoteIndex {
             client,
             lock,
             etags: Vec::new(),
+            token: None,
+            auth_required: false,
+            retry: http.retry.unwrap_or(DEFAULT_RETRY),
         })
     }
 
+    /// Send `req`, retrying spurious failures with an increasing delay between attempts
+    fn execute(
+        &self,
+        req: tame_index::external::reqwest::blocking::Request,
+    ) -> CargoResult<tame_index::external::reqwest::blocking::Response> {
+        let mut remaining = self.retry;
+        let mut delay = std::time::Duration::from_millis(500);
+        loop {
+            let attempt = match req.try_clone() {
+                Some(attempt) if remaining > 0 => attempt,
+                _ => return Ok(self.client.execute(req)?),
+            };
+            match self.client.execute(attempt) {
+                Ok(res) if !is_spurious_status(res.status()) => return Ok(res),
+                Err(err) if !(err.is_timeout() || err.is_connect()) => return Err(err.into()),
+                _ => {}
+            }
+            remaining -= 1;
+            std::thread::sleep(delay);
+            delay *= 2;
+        }
+    }
+
+    /// Attach the token, once the registry has asked for one
+    fn authorize(
+        &self,
+        req: &mut tame_index::external::reqwest::blocking::Request,
+    ) -> CargoResult<()> {
+        if let (true, Some(token)) = (self.auth_required, &self.token) {
+            req.headers_mut().insert(
+                tame_index::external::reqwest::header::AUTHORIZATION,
+                token.parse()?,
+            );
+        }
+        Ok(())
+    }
+
     pub(crate) fn krate(&mut self, name: &str) -> CargoResult<Option<IndexKrate>> {
         let etag = self
             .etags
@@ -221,7 +407,21 @@ impl RemoteIndex {
         let mut req = self.client.request(method, uri.to_string());
         req = req.version(version);
         req = req.headers(headers);
-        let res = self.client.execute(req.build()?)?;
+        let mut req = req.build()?;
+        let unauthorized = req.try_clone();
+        self.authorize(&mut req)?;
+        let mut res = self.execute(req)?;
+        // Like cargo, only hand the token to registries that turn away anonymous reads
+        if res.status() == tame_index::external::reqwest::StatusCode::UNAUTHORIZED
+            && !self.auth_required
+            && self.token.is_some()
+        {
+            if let Some(mut req) = unauthorized {
+                self.auth_required = true;
+                self.authorize(&mut req)?;
+                res = self.execute(req)?;
+            }
+        }
 
         // Grab the etag if it exists for future requests
         if let Some(etag) = res
@@ -256,3 +456,77 @@ impl RemoteIndex {
             .map_err(Into::into)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spurious_statuses_are_retried() {
+        use tame_index::external::reqwest::StatusCode;
+
+        assert!(is_spurious_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_spurious_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_spurious_status(StatusCode::NOT_FOUND));
+        assert!(!is_spurious_status(StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn directory_source_versions() {
+        let root = assert_fs::TempDir::new().unwrap();
+        let root = root.path();
+        for (dir, version) in [
+            ("foo", "1.0.0"),
+            ("foo-0.9.0", "0.9.0"),
+            ("foo-bar", "2.0.0"),
+        ] {
+            let name = if dir == "foo-bar" { "foo-bar" } else { "foo" };
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(
+                root.join(dir).join("Cargo.toml"),
+                format!("[package]\nname = \"{name}\"\nversion = \"{version}\"\n"),
+            )
+            .unwrap();
+            std::fs::write(
+                root.join(dir).join(".cargo-checksum.json"),
+                r#"{"files":{},"package":null}"#,
+            )
+            .unwrap();
+        }
+
+        let registry = RegistryIndex {
+            name: "vendored-sources".to_owned(),
+            url: Url::from_directory_path(root).unwrap(),
+            kind: IndexKind::Directory,
+        };
+        let mut index =
+            AnyIndex::open(&registry, CertsSource::default(), &HttpConfig::default()).unwrap();
+        let foo = index.krate("foo");
+        let missing = index.krate("missing");
+
+        let mut versions = foo
+            .unwrap()
+            .unwrap()
+            .versions
+            .into_iter()
+            .map(|v| v.version.to_string())
+            .collect::<Vec<_>>();
+        versions.sort();
+        assert_eq!(versions, ["0.9.0", "1.0.0"]);
+        assert!(missing.unwrap().is_none());
+    }
+
+    #[test]
+    fn empty_directory_source() {
+        let root = assert_fs::TempDir::new().unwrap();
+        let registry = RegistryIndex {
+            name: "vendored-sources".to_owned(),
+            url: Url::from_directory_path(root.path()).unwrap(),
+            kind: IndexKind::Directory,
+        };
+
+        let index = AnyIndex::open(&registry, CertsSource::default(), &HttpConfig::default());
+
+        assert!(matches!(index, Ok(AnyIndex::Directory(_))));
+    }
+}
@@ -42,7 +42,9 @@ pub use fetch::{get_compatible_dependency, get_latest_dependency, RustVersion};
 pub use index::*;
 pub use manifest::{find, get_dep_version, set_dep_version, LocalManifest, Manifest};
 pub use metadata::manifest_from_pkgid;
-pub use registry::registry_url;
+pub use registry::{
+    http_config, registry_index, registry_token, registry_url, HttpConfig, IndexKind, RegistryIndex,
+};
 pub use util::{
     colorize_stderr, shell_note, shell_print, shell_status, shell_warn, shell_write_stderr,
     shell_write_stdout, Color, ColorChoice,
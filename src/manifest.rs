@@ -1,4 +1,5 @@
 use std::fs;
+use std::io::Write as _;
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 use std::{env, str};
@@ -219,7 +220,9 @@ impl LocalManifest {
             anyhow::bail!("can only edit absolute paths, got {}", path.display());
         }
         let data = fs::read_to_string(path).with_context(|| "Failed to read manifest contents")?;
-        let manifest = data.parse().context("Unable to parse Cargo.toml")?;
+        let manifest = data
+            .parse()
+            .with_context(|| format!("Unable to parse {}", path.display()))?;
         Ok(LocalManifest {
             manifest,
             path: path.to_owned(),
@@ -227,11 +230,22 @@ impl LocalManifest {
     }
 
     /// Write changes back to the file
+    ///
+    /// The file is left untouched if its contents would not change.
     pub fn write(&self) -> CargoResult<()> {
-        let s = self.manifest.data.to_string();
+        let mut s = self.manifest.data.to_string();
+        // `toml_edit` always renders `\n`, so match the line endings already on disk
+        let existing = fs::read_to_string(&self.path).ok();
+        if existing.as_deref().map(uses_crlf).unwrap_or(false) {
+            s = to_crlf(&s);
+        }
+        if existing.as_deref() == Some(s.as_str()) {
+            // Avoid bumping the mtime, which would trigger rebuilds and file watchers
+            return Ok(());
+        }
         let new_contents_bytes = s.as_bytes();
 
-        fs::write(&self.path, new_contents_bytes).context("Failed to write updated Cargo.toml")
+        write_atomic(&self.path, new_contents_bytes).context("Failed to write updated Cargo.toml")
     }
 
     /// Remove entry from a Cargo.toml.
@@ -268,6 +282,21 @@ impl LocalManifest {
         // remove table if empty
         if parent_table.as_table_like().unwrap().is_empty() {
             *parent_table = toml_edit::Item::None;
+
+            // ... along with any `target.<cfg>` tables that are now empty. Other parents stay,
+            // e.g. an empty `[workspace]` still marks the workspace root.
+            let ancestors = if table_path.first().map(String::as_str) == Some("target") {
+                1..table_path.len()
+            } else {
+                0..0
+            };
+            for len in ancestors.rev() {
+                let table = self.get_table_mut(&table_path[..len])?;
+                match table.as_table_like() {
+                    Some(t) if t.is_empty() => *table = toml_edit::Item::None,
+                    _ => break,
+                }
+            }
         }
 
         Ok(())
@@ -383,7 +412,7 @@ impl LocalManifest {
     fn dep_feature(&self, dep_key: &str) -> FeatureStatus {
         let mut status = FeatureStatus::None;
         for (_, tbl) in self.get_sections() {
-            if let toml_edit::Item::Table(tbl) = tbl {
+            if let Some(tbl) = tbl.as_table_like() {
                 if let Some(dep_item) = tbl.get(dep_key) {
                     let optional = dep_item.get("optional");
                     let optional = optional.and_then(|i| i.as_value());
@@ -461,6 +490,47 @@ pub fn find(specified: Option<&Path>) -> CargoResult<PathBuf> {
     }
 }
 
+/// Whether the majority of lines in `text` end in `\r\n`
+fn uses_crlf(text: &str) -> bool {
+    let lines = text.matches('\n').count();
+    let crlf_lines = text.matches("\r\n").count();
+    lines < 2 * crlf_lines
+}
+
+/// Convert every line ending in `text` to `\r\n`
+fn to_crlf(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\n', "\r\n")
+}
+
+/// Replace the contents of `path` without ever leaving it partially written
+///
+/// The contents are written to a temporary file in the same directory, which is then renamed
+/// over the original, carrying over its permissions.
+fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    // Replace the file a symlink points to, rather than the symlink itself
+    let path = dunce::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let dir = path.parent().expect("manifest paths are absolute");
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(path.file_name().expect("manifest paths have a file name"));
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = dir.join(tmp_name);
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+        if let Ok(metadata) = fs::metadata(&path) {
+            fs::set_permissions(&tmp_path, metadata.permissions())?;
+        }
+        fs::rename(&tmp_path, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
 /// Get a dependency's version from its entry in the dependency table
 pub fn get_dep_version(dep_item: &toml_edit::Item) -> CargoResult<&str> {
     if let Some(req) = dep_item.as_str() {
@@ -509,3 +579,223 @@ fn overwrite_value(item: &mut toml_edit::Item, value: impl Into<toml_edit::Value
 pub fn str_or_1_len_table(item: &toml_edit::Item) -> bool {
     item.is_str() || item.as_table_like().map(|t| t.len() == 1).unwrap_or(false)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dotted_dependency_tables() {
+        let mut manifest = LocalManifest {
+            path: PathBuf::from("/Cargo.toml"),
+            manifest: r#"dependencies.serde.version = "1"
+dependencies.serde.features = ["derive"]
+dependencies.log = "0.4"
+target.'cfg(unix)'.dependencies.libc.version = "0.2"
+
+[package]
+name = "foo"
+"#
+            .parse()
+            .unwrap(),
+        };
+        for table in manifest.get_dependency_tables_mut() {
+            for (_, dep_item) in table.iter_mut() {
+                set_dep_version(dep_item, "2").unwrap();
+            }
+        }
+
+        assert_eq!(
+            manifest.to_string(),
+            r#"dependencies.serde.version = "2"
+dependencies.serde.features = ["derive"]
+dependencies.log = "2"
+target.'cfg(unix)'.dependencies.libc.version = "2"
+
+[package]
+name = "foo"
+"#
+        );
+    }
+
+    #[test]
+    fn gc_dep_in_inline_dependency_table() {
+        let mut manifest = LocalManifest {
+            path: PathBuf::from("/Cargo.toml"),
+            manifest: r#"dependencies = { foo = "1", bar = { version = "1", optional = true } }
+
+[package]
+name = "baz"
+
+[features]
+default = ["foo/std", "bar"]
+"#
+            .parse()
+            .unwrap(),
+        };
+        manifest.gc_dep("foo");
+        manifest.gc_dep("bar");
+
+        assert_eq!(
+            manifest.data["features"]["default"].to_string(),
+            r#" ["foo/std", "bar"]"#
+        );
+    }
+
+    #[test]
+    fn remove_last_target_dependency() {
+        let mut manifest = LocalManifest {
+            path: PathBuf::from("/Cargo.toml"),
+            manifest: r#"[package]
+name = "foo"
+
+[target.'cfg(windows)'.dependencies]
+winapi = "0.3"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+[target.'cfg(unix)'.dev-dependencies]
+nix = "0.27"
+"#
+            .parse()
+            .unwrap(),
+        };
+
+        let windows = ["target", "cfg(windows)", "dependencies"].map(String::from);
+        manifest.remove_from_table(&windows, "winapi").unwrap();
+        let unix = ["target", "cfg(unix)", "dependencies"].map(String::from);
+        manifest.remove_from_table(&unix, "libc").unwrap();
+        assert_eq!(
+            manifest.to_string(),
+            r#"[package]
+name = "foo"
+
+[target.'cfg(unix)'.dev-dependencies]
+nix = "0.27"
+"#
+        );
+
+        let unix_dev = ["target", "cfg(unix)", "dev-dependencies"].map(String::from);
+        manifest.remove_from_table(&unix_dev, "nix").unwrap();
+        assert!(!manifest.data.contains_key("target"));
+    }
+
+    #[test]
+    fn remove_last_workspace_dependency() {
+        let mut manifest = LocalManifest {
+            path: PathBuf::from("/Cargo.toml"),
+            manifest: r#"[workspace]
+
+[workspace.dependencies]
+serde = "1"
+"#
+            .parse()
+            .unwrap(),
+        };
+
+        let workspace = ["workspace", "dependencies"].map(String::from);
+        manifest.remove_from_table(&workspace, "serde").unwrap();
+        assert_eq!(manifest.to_string(), "[workspace]\n");
+    }
+
+    #[test]
+    fn detect_crlf() {
+        assert!(uses_crlf("[package]\r\nname = \"a\"\r\n"));
+        assert!(uses_crlf("[package]\r\nname = \"a\"\r\n\n"));
+        assert!(!uses_crlf("[package]\nname = \"a\"\r\n\n"));
+        assert!(!uses_crlf("[package]\nname = \"a\"\n"));
+        assert!(!uses_crlf(""));
+    }
+
+    #[test]
+    fn convert_to_crlf() {
+        assert_eq!(
+            to_crlf("[package]\r\nname = \"a\"\n\n"),
+            "[package]\r\nname = \"a\"\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn write_atomic_replaces_contents() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("Cargo.toml");
+        fs::write(&path, "[package]\nname = \"old\"\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+        let permissions = fs::metadata(&path).unwrap().permissions();
+
+        write_atomic(&path, b"[package]\nname = \"new\"\n").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[package]\nname = \"new\"\n"
+        );
+        assert_eq!(fs::metadata(&path).unwrap().permissions(), permissions);
+        let tmp_path = dir
+            .path()
+            .join(format!(".Cargo.toml.{}.tmp", std::process::id()));
+        assert!(!tmp_path.exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn write_atomic_follows_symlinks() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let target = dir.path().join("real.toml");
+        let link = dir.path().join("Cargo.toml");
+        fs::write(&target, "[package]\nname = \"old\"\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_atomic(&link, b"[package]\nname = \"new\"\n").unwrap();
+
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), target);
+        assert_eq!(
+            fs::read_to_string(&target).unwrap(),
+            "[package]\nname = \"new\"\n"
+        );
+    }
+
+    #[test]
+    fn write_skips_unchanged_manifest() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("Cargo.toml");
+        fs::write(&path, "[package]\nname = \"a\"\n").unwrap();
+        let mtime = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(3600);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        LocalManifest::try_new(&path).unwrap().write().unwrap();
+
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), mtime);
+    }
+
+    #[test]
+    fn write_keeps_crlf() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("Cargo.toml");
+        fs::write(
+            &path,
+            "[package]\r\nname = \"a\"\r\n\r\n[dependencies]\r\nfoo = \"1.0\"\r\n",
+        )
+        .unwrap();
+
+        let mut manifest = LocalManifest::try_new(&path).unwrap();
+        set_dep_version(&mut manifest.data["dependencies"]["foo"], "2.0").unwrap();
+        manifest.write().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[package]\r\nname = \"a\"\r\n\r\n[dependencies]\r\nfoo = \"2.0\"\r\n"
+        );
+    }
+}
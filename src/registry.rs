@@ -1,6 +1,7 @@
 use super::errors::*;
 use std::collections::HashMap;
 use std::path::Path;
+use std::path::PathBuf;
 use url::Url;
 
 const CRATES_IO_INDEX: &str = tame_index::index::sparse::CRATES_IO_HTTP_INDEX;
@@ -8,75 +9,111 @@ const CRATES_IO_REGISTRY: &str = "crates-io";
 
 /// Find the URL of a registry
 pub fn registry_url(manifest_path: &Path, registry: Option<&str>) -> CargoResult<Url> {
-    // TODO support local registry sources, directory sources, git sources: https://doc.rust-lang.org/cargo/reference/source-replacement.html?highlight=replace-with#source-replacement
-    fn read_config(
-        registries: &mut HashMap<String, Source>,
-        path: impl AsRef<Path>,
-    ) -> CargoResult<()> {
-        // TODO unit test for source replacement
-        let content = std::fs::read_to_string(path)?;
-        let config = toml::from_str::<CargoConfig>(&content).map_err(|_| invalid_cargo_config())?;
-        for (key, value) in config.registries {
-            registries.entry(key).or_insert(Source {
-                registry: value.index,
-                replace_with: None,
-            });
-        }
-        for (key, value) in config.source {
-            registries.entry(key).or_insert(value);
-        }
-        Ok(())
-    }
+    registry_index(manifest_path, registry).map(|index| index.url)
+}
+
+/// Where a registry's crates are read from, after source replacement
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RegistryIndex {
+    /// Name of the source that is read, e.g. the `replace-with` target of `crates-io`
+    pub name: String,
+    /// Location of the index, or of the unpacked crates for a `directory` source
+    pub url: Url,
+    /// How the crates at `url` are laid out
+    pub kind: IndexKind,
+}
+
+/// The kind of source a [`RegistryIndex`] points at
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexKind {
+    /// A remote or `file://` registry index
+    #[default]
+    Registry,
+    /// The index of a `local-registry` source
+    LocalRegistry,
+    /// Crates unpacked by `cargo vendor` for a `directory` source
+    Directory,
+}
+
+/// Find the index of a registry, following source replacement
+pub fn registry_index(manifest_path: &Path, registry: Option<&str>) -> CargoResult<RegistryIndex> {
     // registry might be replaced with another source
     // it's looks like a singly linked list
     // put relations in this map.
     let mut registries: HashMap<String, Source> = HashMap::new();
-    // ref: https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure
-    for work_dir in manifest_path
-        .parent()
-        .expect("there must be a parent directory")
-        .ancestors()
-    {
-        let work_cargo_dir = work_dir.join(".cargo");
-        let config_path = work_cargo_dir.join("config");
-        if config_path.is_file() {
-            read_config(&mut registries, config_path)?;
-        } else {
-            let config_path = work_cargo_dir.join("config.toml");
-            if config_path.is_file() {
-                read_config(&mut registries, config_path)?;
-            }
-        }
+    for config_path in config_paths(manifest_path)? {
+        let content = std::fs::read_to_string(&config_path)?;
+        read_config(&mut registries, &content, config_base_dir(&config_path))?;
     }
 
-    let default_cargo_home = home::cargo_home()?;
-    let default_config_path = default_cargo_home.join("config");
-    if default_config_path.is_file() {
-        read_config(&mut registries, default_config_path)?;
-    } else {
-        let default_config_path = default_cargo_home.join("config.toml");
-        if default_config_path.is_file() {
-            read_config(&mut registries, default_config_path)?;
+    let index = registry.and_then(|r| std::env::var(registry_env_var(r, "INDEX")).ok());
+    resolve_registry_index(registries, registry, index)
+}
+
+/// Add the registries and sources from one config file that a higher-precedence file left unset
+fn read_config(
+    registries: &mut HashMap<String, Source>,
+    content: &str,
+    base: &Path,
+) -> CargoResult<()> {
+    // TODO support git sources: https://doc.rust-lang.org/cargo/reference/source-replacement.html?highlight=replace-with#source-replacement
+    let config = toml::from_str::<CargoConfig>(content).map_err(|_| invalid_cargo_config())?;
+    for (key, value) in config.registries {
+        registries.entry(key).or_insert(Source {
+            registry: value.index,
+            ..Default::default()
+        });
+    }
+    for (key, mut value) in config.source {
+        let dir = match (value.directory.take(), value.local_registry.take()) {
+            (Some(dir), _) => Some((base.join(dir), IndexKind::Directory)),
+            (None, Some(dir)) => Some((base.join(dir).join("index"), IndexKind::LocalRegistry)),
+            (None, None) => None,
+        };
+        if let Some((dir, kind)) = dir {
+            value.kind = kind;
+            let url = Url::from_directory_path(dir).map_err(|()| invalid_cargo_config())?;
+            value.registry.get_or_insert_with(|| url.to_string());
         }
+        registries.entry(key).or_insert(value);
     }
+    Ok(())
+}
 
+/// Follow source replacement from `registry` to the index that is actually read
+///
+/// `index` overrides the index of a named registry, as `CARGO_REGISTRIES_<NAME>_INDEX` does.
+fn resolve_registry_index(
+    mut registries: HashMap<String, Source>,
+    registry: Option<&str>,
+    index: Option<String>,
+) -> CargoResult<RegistryIndex> {
     // find head of the relevant linked list
-    let mut source = match registry {
+    let (mut name, mut source) = match registry {
         Some(CRATES_IO_INDEX) | None => {
             let mut source = registries.remove(CRATES_IO_REGISTRY).unwrap_or_default();
             source
                 .registry
                 .get_or_insert_with(|| CRATES_IO_INDEX.to_string());
-            source
+            (CRATES_IO_REGISTRY.to_owned(), source)
+        }
+        Some(r) => {
+            let mut source = registries
+                .remove(r)
+                .or_else(|| index.is_some().then(Source::default))
+                .with_context(|| anyhow::format_err!("The registry '{}' could not be found", r))?;
+            if let Some(index) = index {
+                source.registry = Some(index);
+            }
+            (r.to_owned(), source)
         }
-        Some(r) => registries
-            .remove(r)
-            .with_context(|| anyhow::format_err!("The registry '{}' could not be found", r))?,
     };
 
     // search this linked list and find the tail
     while let Some(replace_with) = &source.replace_with {
         let is_crates_io = replace_with == CRATES_IO_INDEX;
+        name.clone_from(replace_with);
         source = registries.remove(replace_with).with_context(|| {
             anyhow::format_err!("The source '{}' could not be found", replace_with)
         })?;
@@ -87,12 +124,161 @@ pub fn registry_url(manifest_path: &Path, registry: Option<&str>) -> CargoResult
         }
     }
 
-    let registry_url = source
+    let url = source
         .registry
         .and_then(|x| Url::parse(&x).ok())
         .with_context(invalid_cargo_config)?;
 
-    Ok(registry_url)
+    Ok(RegistryIndex {
+        name,
+        url,
+        kind: source.kind,
+    })
+}
+
+/// Network settings from cargo's `[http]` and `[net]` config tables
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HttpConfig {
+    /// Extra certificate authorities to trust, as a PEM bundle (`http.cainfo`)
+    pub cainfo: Option<PathBuf>,
+    /// Proxy for all requests (`http.proxy`), in place of the `*_proxy` environment variables
+    pub proxy: Option<String>,
+    /// Timeout for each request (`http.timeout`)
+    pub timeout: Option<std::time::Duration>,
+    /// Number of times to retry spurious network errors (`net.retry`)
+    pub retry: Option<u32>,
+}
+
+/// Read the network settings that apply to a manifest
+pub fn http_config(manifest_path: &Path) -> CargoResult<HttpConfig> {
+    let mut http = HttpConfig {
+        cainfo: std::env::var_os("CARGO_HTTP_CAINFO").map(PathBuf::from),
+        proxy: std::env::var("CARGO_HTTP_PROXY").ok(),
+        timeout: env_number("CARGO_HTTP_TIMEOUT")?.map(std::time::Duration::from_secs),
+        retry: env_number("CARGO_NET_RETRY")?,
+    };
+
+    for config_path in config_paths(manifest_path)? {
+        let content = std::fs::read_to_string(&config_path)?;
+        merge_http_config(&mut http, &content, config_base_dir(&config_path))?;
+    }
+
+    Ok(http)
+}
+
+/// Fill in the settings from one config file that a higher-precedence source left unset
+fn merge_http_config(http: &mut HttpConfig, content: &str, base: &Path) -> CargoResult<()> {
+    let config = toml::from_str::<CargoConfig>(content).map_err(|_| invalid_cargo_config())?;
+    http.cainfo = http
+        .cainfo
+        .take()
+        .or_else(|| config.http.cainfo.map(|p| base.join(p)));
+    http.proxy = http.proxy.take().or(config.http.proxy);
+    http.timeout = http
+        .timeout
+        .or(config.http.timeout.map(std::time::Duration::from_secs));
+    http.retry = http.retry.or(config.net.retry);
+    Ok(())
+}
+
+fn env_number<T>(key: &str) -> CargoResult<Option<T>>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match std::env::var(key) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .with_context(|| format!("{key} must be a number, got `{value}`")),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Cargo config files that apply to a manifest, from highest to lowest precedence
+fn config_paths(manifest_path: &Path) -> CargoResult<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    // ref: https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure
+    for work_dir in manifest_path
+        .parent()
+        .expect("there must be a parent directory")
+        .ancestors()
+    {
+        let work_cargo_dir = work_dir.join(".cargo");
+        let config_path = work_cargo_dir.join("config");
+        if config_path.is_file() {
+            paths.push(config_path);
+        } else {
+            let config_path = work_cargo_dir.join("config.toml");
+            if config_path.is_file() {
+                paths.push(config_path);
+            }
+        }
+    }
+
+    let default_cargo_home = home::cargo_home()?;
+    let default_config_path = default_cargo_home.join("config");
+    if default_config_path.is_file() {
+        paths.push(default_config_path);
+    } else {
+        let default_config_path = default_cargo_home.join("config.toml");
+        if default_config_path.is_file() {
+            paths.push(default_config_path);
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Directory that relative paths in a config file are resolved against
+///
+/// Cargo resolves them relative to the directory containing `.cargo/`.
+fn config_base_dir(config_path: &Path) -> &Path {
+    let cargo_dir = config_path.parent().unwrap_or(config_path);
+    cargo_dir.parent().unwrap_or(cargo_dir)
+}
+
+/// Find the token to authenticate with a registry
+///
+/// Only named registries are considered; crates.io index reads never need a token.
+pub fn registry_token(registry: Option<&str>) -> CargoResult<Option<String>> {
+    let registry = match registry {
+        Some(CRATES_IO_REGISTRY) | None => return Ok(None),
+        Some(r) => r,
+    };
+
+    if let Ok(token) = std::env::var(registry_env_var(registry, "TOKEN")) {
+        return Ok(Some(token));
+    }
+
+    let cargo_home = home::cargo_home()?;
+    let mut credentials_path = cargo_home.join("credentials.toml");
+    if !credentials_path.is_file() {
+        credentials_path = cargo_home.join("credentials");
+        if !credentials_path.is_file() {
+            return Ok(None);
+        }
+    }
+    let content = std::fs::read_to_string(&credentials_path)?;
+    token_from_credentials(&content, registry)
+        .with_context(|| format!("Unable to parse {}", credentials_path.display()))
+}
+
+/// Cargo's environment override for `registries.<name>.<key>`
+fn registry_env_var(registry: &str, key: &str) -> String {
+    format!(
+        "CARGO_REGISTRIES_{}_{key}",
+        registry.to_uppercase().replace('-', "_")
+    )
+}
+
+fn token_from_credentials(content: &str, registry: &str) -> CargoResult<Option<String>> {
+    let mut credentials = toml::from_str::<CargoCredentials>(content)?;
+    Ok(credentials
+        .registries
+        .remove(registry)
+        .and_then(|r| r.token))
 }
 
 #[derive(Debug, Deserialize)]
@@ -101,6 +287,10 @@ struct CargoConfig {
     registries: HashMap<String, Registry>,
     #[serde(default)]
     source: HashMap<String, Source>,
+    #[serde(default)]
+    http: Http,
+    #[serde(default)]
+    net: Net,
 }
 
 #[derive(Default, Debug, Deserialize)]
@@ -108,6 +298,11 @@ struct Source {
     #[serde(rename = "replace-with")]
     replace_with: Option<String>,
     registry: Option<String>,
+    #[serde(rename = "local-registry")]
+    local_registry: Option<String>,
+    directory: Option<String>,
+    #[serde(skip)]
+    kind: IndexKind,
 }
 
 #[derive(Debug, Deserialize)]
@@ -115,6 +310,29 @@ struct Registry {
     index: Option<String>,
 }
 
+#[derive(Default, Debug, Deserialize)]
+struct Http {
+    cainfo: Option<String>,
+    proxy: Option<String>,
+    timeout: Option<u64>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+struct Net {
+    retry: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CargoCredentials {
+    #[serde(default)]
+    registries: HashMap<String, RegistryCredentials>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistryCredentials {
+    token: Option<String>,
+}
+
 mod code_from_cargo {
     #![allow(dead_code)]
 
@@ -135,3 +353,156 @@ mod code_from_cargo {
         DefaultBranch,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_for_named_registry() {
+        let credentials = r#"
+[registry]
+token = "crates-io-token"
+
+[registries.corp]
+token = "corp-token"
+
+[registries.other]
+"#;
+        assert_eq!(
+            token_from_credentials(credentials, "corp").unwrap(),
+            Some("corp-token".to_owned())
+        );
+        assert_eq!(token_from_credentials(credentials, "other").unwrap(), None);
+        assert_eq!(
+            token_from_credentials(credentials, "missing").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn env_var_for_registry() {
+        assert_eq!(
+            registry_env_var("corp", "TOKEN"),
+            "CARGO_REGISTRIES_CORP_TOKEN"
+        );
+        assert_eq!(
+            registry_env_var("my-registry", "INDEX"),
+            "CARGO_REGISTRIES_MY_REGISTRY_INDEX"
+        );
+    }
+
+    #[test]
+    fn http_settings_from_config() {
+        let base = Path::new("/project");
+        let mut http = HttpConfig {
+            proxy: Some("env-proxy:8080".to_owned()),
+            ..Default::default()
+        };
+        merge_http_config(
+            &mut http,
+            r#"
+[http]
+cainfo = "certs/corp.pem"
+proxy = "proxy.example.com:3128"
+
+[net]
+retry = 5
+"#,
+            base,
+        )
+        .unwrap();
+        merge_http_config(
+            &mut http,
+            r#"
+[http]
+cainfo = "/etc/ssl/other.pem"
+timeout = 60
+
+[net]
+retry = 1
+"#,
+            base,
+        )
+        .unwrap();
+
+        assert_eq!(http.cainfo, Some(base.join("certs/corp.pem")));
+        assert_eq!(http.proxy.as_deref(), Some("env-proxy:8080"));
+        assert_eq!(http.timeout, Some(std::time::Duration::from_secs(60)));
+        assert_eq!(http.retry, Some(5));
+    }
+
+    #[test]
+    fn source_replacement() {
+        // Never touched, only used to resolve relative paths
+        let base = std::env::temp_dir().join("project");
+        let config = r#"
+[source.crates-io]
+replace-with = "vendored"
+
+[source.vendored]
+replace-with = "mirror"
+
+[source.mirror]
+registry = "https://mirror.example.com/index/"
+
+[source.vendor-dir]
+directory = "vendor"
+
+[source.local]
+local-registry = "registry"
+
+[registries.corp]
+index = "https://corp.example.com/index/"
+"#;
+        let registries = || {
+            let mut registries = HashMap::new();
+            read_config(&mut registries, config, &base).unwrap();
+            registries
+        };
+
+        let crates_io = resolve_registry_index(registries(), None, None);
+        let corp = resolve_registry_index(registries(), Some("corp"), None);
+        let corp_from_env = resolve_registry_index(
+            registries(),
+            Some("corp"),
+            Some("https://env.example.com/index/".to_owned()),
+        );
+        let vendor = resolve_registry_index(registries(), Some("vendor-dir"), None);
+        let local = resolve_registry_index(registries(), Some("local"), None);
+        let missing = resolve_registry_index(registries(), Some("missing"), None);
+
+        assert_eq!(
+            crates_io.unwrap(),
+            RegistryIndex {
+                name: "mirror".to_owned(),
+                url: Url::parse("https://mirror.example.com/index/").unwrap(),
+                kind: IndexKind::Registry,
+            }
+        );
+        let corp = corp.unwrap();
+        assert_eq!(corp.name, "corp");
+        assert_eq!(corp.url.as_str(), "https://corp.example.com/index/");
+        assert_eq!(
+            corp_from_env.unwrap().url.as_str(),
+            "https://env.example.com/index/"
+        );
+        assert_eq!(
+            vendor.unwrap(),
+            RegistryIndex {
+                name: "vendor-dir".to_owned(),
+                url: Url::from_directory_path(base.join("vendor")).unwrap(),
+                kind: IndexKind::Directory,
+            }
+        );
+        assert_eq!(
+            local.unwrap(),
+            RegistryIndex {
+                name: "local".to_owned(),
+                url: Url::from_directory_path(base.join("registry").join("index")).unwrap(),
+                kind: IndexKind::LocalRegistry,
+            }
+        );
+        assert!(missing.is_err());
+    }
+}
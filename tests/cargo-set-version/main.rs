@@ -5,6 +5,8 @@
 #[macro_use]
 extern crate cargo_test_macro;
 
+mod color_always;
+mod color_never;
 mod downgrade_error;
 mod dry_run;
 mod ignore_dependent;